@@ -1,14 +1,15 @@
 //! Documentation for botp, a Blake3 implementation of hotp. Which is more secure
-//! as it uses a 11 digit number compared to the 6 digit number of the usual specification.
+//! as it uses a configurable digit count (up to 18) compared to the 6 digit
+//! number of the usual specification.
 //!
 //! # Example
 //! ```
+//! use botp::*;
 //! use std::time::UNIX_EPOCH;
-//! let key = generate_secret_key();
-//! let code = botp(
-//!     get_counter(30, UNIX_EPOCH),
-//!     key
-//! );
+//!
+//! let key = generate_secret_key().unwrap();
+//! let counter = get_counter(30, UNIX_EPOCH).unwrap();
+//! let code = botp(counter, key, 11).unwrap();
 //! ```
 //!
 
@@ -17,61 +18,125 @@ use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
 use std::time::SystemTime;
 
+#[derive(Debug)]
 pub enum Error {
     TimeError,
     RandomBytesError,
+    DecodeError,
+    InvalidDigits,
+    EmptyAlphabet,
+    InsufficientBytes,
 }
 
-trait WrapIndex {
-    type Item;
-    fn wrapped_index(&self, index: usize) -> &Self::Item;
+/// The minimum number of digits a generated code may have.
+pub const MIN_DIGITS: u32 = 6;
+/// The maximum number of digits a generated code may have. A `u64` value
+/// supports roughly 19 decimal digits, so 18 leaves headroom for the modulus.
+pub const MAX_DIGITS: u32 = 18;
+/// The digit count used by [`totp`] and [`verify_totp`].
+pub const DEFAULT_DIGITS: u32 = 11;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Encodes a 32-byte secret as an unpadded RFC 4648 Base32 string, so it can
+/// be typed into or scanned by standard authenticator apps (Google
+/// Authenticator, 1Password, etc.) that expect Base32-encoded keys.
+pub fn encode_secret(secret: &[u8; 32]) -> String {
+    let mut output = String::with_capacity(52);
+    let mut buffer: u64 = 0;
+    let mut bits_left: u32 = 0;
+
+    for &byte in secret.iter() {
+        buffer = (buffer << 8) | byte as u64;
+        bits_left += 8;
+
+        while bits_left >= 5 {
+            bits_left -= 5;
+            let index = ((buffer >> bits_left) & 0x1f) as usize;
+            output.push(BASE32_ALPHABET[index] as char);
+        }
+    }
+
+    if bits_left > 0 {
+        let index = ((buffer << (5 - bits_left)) & 0x1f) as usize;
+        output.push(BASE32_ALPHABET[index] as char);
+    }
+
+    output
 }
 
-impl<T> WrapIndex for [T] {
-    type Item = T;
+/// Decodes a Base32-encoded secret back into its raw 32 bytes, tolerating
+/// both padded (`=`) and unpadded input as well as lowercase letters.
+///
+/// Returns [`Error::DecodeError`] if the input contains characters outside
+/// the Base32 alphabet or does not decode to exactly 32 bytes.
+pub fn decode_secret(s: &str) -> Result<[u8; 32], Error> {
+    let mut buffer: u64 = 0;
+    let mut bits_left: u32 = 0;
+    let mut output = Vec::with_capacity(32);
 
-    /// The wrapped index of any array, allowing for calls that would normally be out of bounds to be within bounds
-    ///
-    /// # Example
-    /// ```
-    /// let x = [1, 2, 3, 4];
-    /// x[5] // Normal use, throws an error
-    /// x.wrapped_index(5) // Wrapped use, would return `1` (the index % size_of_array)
-    fn wrapped_index(&self, index: usize) -> &Self::Item {
-        let wrapped_index = index % self.len();
-        &self[wrapped_index]
+    for c in s.chars() {
+        if c == '=' {
+            continue;
+        }
+
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u64 - 'A' as u64,
+            c @ '2'..='7' => c as u64 - '2' as u64 + 26,
+            _ => return Err(Error::DecodeError),
+        };
+
+        buffer = (buffer << 5) | value;
+        bits_left += 5;
+
+        if bits_left >= 8 {
+            bits_left -= 8;
+            output.push(((buffer >> bits_left) & 0xff) as u8);
+        }
     }
+
+    output.try_into().map_err(|_| Error::DecodeError)
 }
 
-pub fn botp(counter: u64, secret: [u8; 32]) -> u64 {
-    let counter_ne: [u8; 8] = counter.to_be_bytes();
+/// Generates a `digits`-digit one-time code for `counter` using `secret`.
+///
+/// Returns [`Error::InvalidDigits`] if `digits` falls outside
+/// [`MIN_DIGITS`]..=[`MAX_DIGITS`].
+pub fn botp(counter: u64, secret: [u8; 32], digits: u32) -> Result<u64, Error> {
+    if !(MIN_DIGITS..=MAX_DIGITS).contains(&digits) {
+        return Err(Error::InvalidDigits);
+    }
+
+    let counter_be: [u8; 8] = counter.to_be_bytes();
 
-    let hash = keyed_hash(&secret, &counter_ne);
+    let hash = keyed_hash(&secret, &counter_be);
     let hash_bytes: &[u8; 32] = hash.as_bytes();
 
-    truncate(&hash_bytes)
+    Ok(truncate(hash_bytes, digits))
 }
 
-fn truncate(hash_bytes: &[u8; 32]) -> u64 {
-    let offset: usize = ((hash_bytes[31]) % 28) as usize;
-    println!("{:?}", offset);
+/// RFC 4226 dynamic truncation: the low nibble of the final hash byte picks
+/// an in-bounds offset, a big-endian integer is assembled from the window
+/// starting there (masking the high bit of its first byte), and the result
+/// is reduced modulo `10^digits`.
+fn truncate(hash_bytes: &[u8; 32], digits: u32) -> u64 {
+    let offset = (hash_bytes[31] & 0x0f) as usize;
 
-    let binned_code: u64 = u64::from_be_bytes([
-        (hash_bytes.wrapped_index(offset) & 0x7f),
-        (hash_bytes.wrapped_index(offset + 1) & 0xff),
-        (hash_bytes.wrapped_index(offset + 2) & 0xff),
-        (hash_bytes.wrapped_index(offset + 3) & 0xff),
-        // Next 8 bytes
-        (hash_bytes.wrapped_index(offset + 4) & 0xff),
-        (hash_bytes.wrapped_index(offset + 5) & 0xff),
-        (hash_bytes.wrapped_index(offset + 6) & 0xff),
-        (hash_bytes.wrapped_index(offset + 7) & 0xff),
-    ]);
-    println!("Binned code: {:?}", binned_code);
+    let value: u64 = if digits <= 9 {
+        u32::from_be_bytes([
+            hash_bytes[offset] & 0x7f,
+            hash_bytes[offset + 1],
+            hash_bytes[offset + 2],
+            hash_bytes[offset + 3],
+        ]) as u64
+    } else {
+        let mut window = [0u8; 8];
+        window[0] = hash_bytes[offset] & 0x7f;
+        window[1..].copy_from_slice(&hash_bytes[offset + 1..offset + 8]);
+        u64::from_be_bytes(window)
+    };
 
-    let code = binned_code % 100_000_000_000;
-    println!("Code: {:?}", code);
-    code
+    value % 10u64.pow(digits)
 }
 
 pub fn get_counter(interval: u64, epoch: SystemTime) -> Result<u64, Error> {
@@ -81,6 +146,202 @@ pub fn get_counter(interval: u64, epoch: SystemTime) -> Result<u64, Error> {
     }
 }
 
+/// Checks `candidate` against the codes generated for counters
+/// `counter..=counter + look_ahead`, implementing the RFC 4226
+/// resynchronization window for clients whose counter has drifted ahead of
+/// the server's.
+///
+/// Returns the first matching counter so the caller can resynchronize its
+/// stored counter to it, or `None` if nothing in the window matches.
+/// Candidates are compared in constant time to avoid leaking secret-derived
+/// timing information.
+pub fn verify(
+    candidate: u64,
+    counter: u64,
+    secret: [u8; 32],
+    digits: u32,
+    look_ahead: u64,
+) -> Result<Option<u64>, Error> {
+    for offset in 0..=look_ahead {
+        let counter_value = counter + offset;
+        let code = botp(counter_value, secret, digits)?;
+
+        if constant_time_eq(&code.to_be_bytes(), &candidate.to_be_bytes()) {
+            return Ok(Some(counter_value));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Compares two equal-length byte slices in constant time, mirroring the
+/// approach BLAKE3 uses internally for hash equality, so that comparing a
+/// secret-derived code never leaks timing information about where it
+/// diverges from the candidate.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff: u8 = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+
+    diff == 0
+}
+
+/// Generates a TOTP code for the current time step, built on [`get_counter`]
+/// and [`botp`].
+pub fn totp(interval: u64, epoch: SystemTime, secret: [u8; 32]) -> Result<u64, Error> {
+    let counter = get_counter(interval, epoch)?;
+    botp(counter, secret, DEFAULT_DIGITS)
+}
+
+/// Checks `candidate` against the current TOTP step and the `skew_steps`
+/// steps immediately before and after it, to tolerate clock drift between
+/// client and server. Comparisons use the constant-time compare from
+/// [`verify`].
+pub fn verify_totp(
+    candidate: u64,
+    interval: u64,
+    epoch: SystemTime,
+    secret: [u8; 32],
+    skew_steps: u64,
+) -> Result<bool, Error> {
+    let current_step = get_counter(interval, epoch)?;
+    let first_step = current_step.saturating_sub(skew_steps);
+    let last_step = current_step + skew_steps;
+
+    for step in first_step..=last_step {
+        let code = botp(step, secret, DEFAULT_DIGITS)?;
+
+        if constant_time_eq(&code.to_be_bytes(), &candidate.to_be_bytes()) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Fills `out` with `out.len()` pseudorandom bytes derived from `counter` and
+/// `secret` using BLAKE3's keyed extendable-output mode (XOF), for use cases
+/// that need more entropy than a single `u64` code can carry. Unlike
+/// [`botp`], the output length isn't bounded by the 8-byte window
+/// [`truncate`] reads from a fixed-size hash.
+pub fn botp_xof(counter: u64, secret: [u8; 32], out: &mut [u8]) {
+    let counter_be: [u8; 8] = counter.to_be_bytes();
+
+    let mut hasher = blake3::Hasher::new_keyed(&secret);
+    hasher.update(&counter_be);
+    hasher.finalize_xof().fill(out);
+}
+
+/// Maps XOF bytes (as produced by [`botp_xof`]) to a decimal string of
+/// `digits` characters without modulo bias, by rejecting bytes `>= 250` so
+/// that `byte % 10` stays uniformly distributed over `0..=9`.
+///
+/// `bytes` should carry some slack over `digits` (roughly double), since
+/// rejected bytes are skipped. Returns [`Error::InsufficientBytes`] if
+/// `bytes` runs out before `digits` characters are collected, rather than
+/// silently handing back a shorter string.
+pub fn bytes_to_decimal_digits(bytes: &[u8], digits: usize) -> Result<String, Error> {
+    let mut result = String::with_capacity(digits);
+
+    for &byte in bytes {
+        if result.len() == digits {
+            break;
+        }
+        if byte < 250 {
+            result.push((b'0' + byte % 10) as char);
+        }
+    }
+
+    if result.len() < digits {
+        return Err(Error::InsufficientBytes);
+    }
+
+    Ok(result)
+}
+
+/// Renders the RFC 4226 dynamic-truncation window into a fixed-length string
+/// over a custom `alphabet`, e.g. Steam Guard's 5-character codes drawn from
+/// its own character set, instead of `botp`'s decimal digits.
+///
+/// Returns [`Error::EmptyAlphabet`] if `alphabet` is empty.
+pub fn botp_alnum(
+    counter: u64,
+    secret: [u8; 32],
+    alphabet: &[char],
+    length: usize,
+) -> Result<String, Error> {
+    if alphabet.is_empty() {
+        return Err(Error::EmptyAlphabet);
+    }
+
+    let counter_be: [u8; 8] = counter.to_be_bytes();
+
+    let hash = keyed_hash(&secret, &counter_be);
+    let hash_bytes: &[u8; 32] = hash.as_bytes();
+
+    let offset = (hash_bytes[31] & 0x0f) as usize;
+    let mut value = u32::from_be_bytes([
+        hash_bytes[offset] & 0x7f,
+        hash_bytes[offset + 1],
+        hash_bytes[offset + 2],
+        hash_bytes[offset + 3],
+    ]) as u64;
+
+    let base = alphabet.len() as u64;
+    let mut code = String::with_capacity(length);
+    for _ in 0..length {
+        let index = (value % base) as usize;
+        code.push(alphabet[index]);
+        value /= base;
+    }
+
+    Ok(code)
+}
+
+/// Builds an `otpauth://totp` provisioning URI for QR-code enrollment,
+/// reusing [`encode_secret`] for the Base32 secret parameter. The
+/// `algorithm=BLAKE3` parameter documents `botp`'s deviation from the
+/// SHA-family algorithms most authenticator apps expect.
+pub fn provisioning_uri(
+    secret: [u8; 32],
+    label: &str,
+    issuer: &str,
+    digits: u32,
+    period: u64,
+) -> String {
+    let encoded_secret = encode_secret(&secret);
+    let encoded_label = percent_encode(label);
+    let encoded_issuer = percent_encode(issuer);
+
+    format!(
+        "otpauth://totp/{encoded_issuer}:{encoded_label}?secret={encoded_secret}&issuer={encoded_issuer}&digits={digits}&period={period}&algorithm=BLAKE3"
+    )
+}
+
+/// Percent-encodes every byte outside the RFC 3986 unreserved set
+/// (`ALPHA` / `DIGIT` / `-` / `.` / `_` / `~`), so values like a label or
+/// issuer containing spaces, `&`, `:`, or `#` can't corrupt the otpauth URI
+/// they're interpolated into.
+fn percent_encode(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                output.push(byte as char)
+            }
+            _ => output.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    output
+}
+
 pub fn generate_secret_key() -> Result<[u8; 32], Error> {
     let mut rng = ChaCha20Rng::from_entropy();
     let mut secret_key = [0; 32];
@@ -88,3 +349,206 @@ pub fn generate_secret_key() -> Result<[u8; 32], Error> {
         .map_err(|_| Error::RandomBytesError)?;
     Ok(secret_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn base32_round_trips_through_encode_and_decode() {
+        let secret = [7u8; 32];
+        let encoded = encode_secret(&secret);
+        let decoded = decode_secret(&encoded).unwrap();
+
+        assert_eq!(decoded, secret);
+    }
+
+    #[test]
+    fn base32_decode_accepts_padding_and_lowercase() {
+        let secret = [0u8; 32];
+        let encoded = encode_secret(&secret);
+
+        let padded = format!("{encoded}====");
+        assert_eq!(decode_secret(&padded).unwrap(), secret);
+
+        let lowercase = encoded.to_ascii_lowercase();
+        assert_eq!(decode_secret(&lowercase).unwrap(), secret);
+    }
+
+    #[test]
+    fn base32_decode_rejects_invalid_characters() {
+        assert!(matches!(
+            decode_secret("this is not base32!"),
+            Err(Error::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn base32_decode_rejects_wrong_length() {
+        assert!(matches!(decode_secret("AAAA"), Err(Error::DecodeError)));
+    }
+
+    #[test]
+    fn botp_rejects_digits_outside_min_max() {
+        let secret = [1u8; 32];
+
+        assert!(matches!(
+            botp(0, secret, MIN_DIGITS - 1),
+            Err(Error::InvalidDigits)
+        ));
+        assert!(matches!(
+            botp(0, secret, MAX_DIGITS + 1),
+            Err(Error::InvalidDigits)
+        ));
+    }
+
+    #[test]
+    fn botp_is_deterministic_for_the_same_counter_and_secret() {
+        let secret = [2u8; 32];
+
+        assert_eq!(botp(42, secret, 6).unwrap(), botp(42, secret, 6).unwrap());
+    }
+
+    #[test]
+    fn botp_stays_within_the_requested_digit_count_across_the_9_10_boundary() {
+        let secret = [3u8; 32];
+
+        for counter in 0..256u64 {
+            let nine_digit = botp(counter, secret, 9).unwrap();
+            assert!(nine_digit < 10u64.pow(9));
+
+            let ten_digit = botp(counter, secret, 10).unwrap();
+            assert!(ten_digit < 10u64.pow(10));
+        }
+    }
+
+    #[test]
+    fn verify_resynchronizes_within_the_look_ahead_window() {
+        let secret = [4u8; 32];
+        let candidate = botp(10, secret, 6).unwrap();
+
+        assert_eq!(verify(candidate, 5, secret, 6, 10).unwrap(), Some(10));
+    }
+
+    #[test]
+    fn verify_rejects_a_candidate_outside_the_window() {
+        let secret = [4u8; 32];
+        let candidate = botp(10, secret, 6).unwrap();
+
+        assert_eq!(verify(candidate, 5, secret, 6, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn totp_matches_verify_totp_for_the_current_step() {
+        let secret = [5u8; 32];
+
+        let candidate = totp(30, UNIX_EPOCH, secret).unwrap();
+
+        assert!(verify_totp(candidate, 30, UNIX_EPOCH, secret, 0).unwrap());
+    }
+
+    #[test]
+    fn verify_totp_tolerates_clock_skew_within_steps() {
+        let secret = [6u8; 32];
+        let current_step = get_counter(30, UNIX_EPOCH).unwrap();
+        let next_step_code = botp(current_step + 1, secret, DEFAULT_DIGITS).unwrap();
+
+        assert!(verify_totp(next_step_code, 30, UNIX_EPOCH, secret, 1).unwrap());
+    }
+
+    #[test]
+    fn verify_totp_rejects_candidates_outside_the_skew_window() {
+        let secret = [6u8; 32];
+        let current_step = get_counter(30, UNIX_EPOCH).unwrap();
+        let far_step_code = botp(current_step + 5, secret, DEFAULT_DIGITS).unwrap();
+
+        assert!(!verify_totp(far_step_code, 30, UNIX_EPOCH, secret, 1).unwrap());
+    }
+
+    #[test]
+    fn botp_xof_is_deterministic_and_fills_the_requested_length() {
+        let secret = [8u8; 32];
+        let mut first = [0u8; 64];
+        let mut second = [0u8; 64];
+
+        botp_xof(1, secret, &mut first);
+        botp_xof(1, secret, &mut second);
+
+        assert_eq!(first, second);
+        assert_ne!(first[32..], [0u8; 32]);
+    }
+
+    #[test]
+    fn bytes_to_decimal_digits_produces_exactly_digits_characters() {
+        let secret = [9u8; 32];
+        let mut bytes = [0u8; 64];
+        botp_xof(1, secret, &mut bytes);
+
+        let digits = bytes_to_decimal_digits(&bytes, 10).unwrap();
+
+        assert_eq!(digits.len(), 10);
+        assert!(digits.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn bytes_to_decimal_digits_rejects_insufficient_input() {
+        // All bytes are >= 250, so every one is rejected and none are emitted.
+        let bytes = [250u8; 8];
+
+        assert!(matches!(
+            bytes_to_decimal_digits(&bytes, 4),
+            Err(Error::InsufficientBytes)
+        ));
+    }
+
+    #[test]
+    fn botp_alnum_rejects_an_empty_alphabet() {
+        let secret = [10u8; 32];
+
+        assert!(matches!(
+            botp_alnum(0, secret, &[], 5),
+            Err(Error::EmptyAlphabet)
+        ));
+    }
+
+    #[test]
+    fn botp_alnum_produces_length_long_output_from_the_given_alphabet() {
+        let secret = [11u8; 32];
+        let alphabet: Vec<char> = "ABCDEFGHJKMNPQRTVWXY".chars().collect();
+
+        let code = botp_alnum(0, secret, &alphabet, 5).unwrap();
+
+        assert_eq!(code.chars().count(), 5);
+        assert!(code.chars().all(|c| alphabet.contains(&c)));
+    }
+
+    #[test]
+    fn percent_encode_escapes_reserved_and_unsafe_characters() {
+        assert_eq!(percent_encode("My Company"), "My%20Company");
+        assert_eq!(percent_encode("Issuer&Name"), "Issuer%26Name");
+        assert_eq!(percent_encode("a:b#c"), "a%3Ab%23c");
+        assert_eq!(percent_encode("unreserved-._~09AZ"), "unreserved-._~09AZ");
+    }
+
+    #[test]
+    fn provisioning_uri_percent_encodes_label_and_issuer() {
+        let secret = [12u8; 32];
+
+        let uri = provisioning_uri(secret, "a user", "My Company", 6, 30);
+
+        assert!(uri.contains("otpauth://totp/My%20Company:a%20user?"));
+        assert!(uri.contains("issuer=My%20Company"));
+        assert!(!uri.contains(' '));
+    }
+
+    #[test]
+    fn provisioning_uri_prevents_query_string_injection_via_issuer() {
+        let secret = [13u8; 32];
+
+        let uri = provisioning_uri(secret, "user", "Issuer&Name", 6, 30);
+
+        assert!(!uri.contains("issuer=Issuer&Name"));
+        assert!(uri.contains("issuer=Issuer%26Name"));
+    }
+}